@@ -23,19 +23,114 @@ pub struct ComponentBuilder {
     core_memories: u32,
     core_tables: u32,
     core_instances: u32,
+    core_types: u32,
 
     // Component index spaces
     funcs: u32,
     instances: u32,
     types: u32,
+    components: u32,
+    values: u32,
+
+    // Whether or not `start` has been called yet, used to guard against a
+    // component accidentally declaring more than one start function.
+    has_start: bool,
+
+    // Debug names, all optional, collected as the builder is used and
+    // flushed into a final `ComponentNameSection` at the very end during
+    // `finish()`.
+    component_name: Option<String>,
+    core_func_names: Option<NameMap>,
+    core_module_names: Option<NameMap>,
+    core_instance_names: Option<NameMap>,
+    func_names: Option<NameMap>,
+    instance_names: Option<NameMap>,
+    type_names: Option<NameMap>,
+    component_names: Option<NameMap>,
 }
 
 impl ComponentBuilder {
     pub fn finish(mut self) -> Vec<u8> {
         self.flush();
+
+        let mut names = ComponentNameSection::new();
+        let mut any_names = false;
+        if let Some(name) = &self.component_name {
+            names.component(name);
+            any_names = true;
+        }
+        macro_rules! flush_names {
+            ($($field:ident => $method:ident)*) => ($(
+                if let Some(map) = &self.$field {
+                    names.$method(map);
+                    any_names = true;
+                }
+            )*)
+        }
+        flush_names! {
+            core_func_names => core_funcs
+            core_module_names => core_modules
+            core_instance_names => core_instances
+            func_names => funcs
+            instance_names => instances
+            type_names => types
+            component_names => components
+        }
+        if any_names {
+            self.component.section(&names);
+        }
+
         self.component.finish()
     }
 
+    /// Sets the name of this component itself, used for the top-level entry
+    /// of the emitted `ComponentNameSection`.
+    pub fn set_component_name(&mut self, name: &str) {
+        self.component_name = Some(name.to_string());
+    }
+
+    pub fn name_core_func(&mut self, idx: u32, name: &str) {
+        self.core_func_names
+            .get_or_insert_with(NameMap::new)
+            .append(idx, name);
+    }
+
+    pub fn name_core_module(&mut self, idx: u32, name: &str) {
+        self.core_module_names
+            .get_or_insert_with(NameMap::new)
+            .append(idx, name);
+    }
+
+    pub fn name_core_instance(&mut self, idx: u32, name: &str) {
+        self.core_instance_names
+            .get_or_insert_with(NameMap::new)
+            .append(idx, name);
+    }
+
+    pub fn name_func(&mut self, idx: u32, name: &str) {
+        self.func_names
+            .get_or_insert_with(NameMap::new)
+            .append(idx, name);
+    }
+
+    pub fn name_instance(&mut self, idx: u32, name: &str) {
+        self.instance_names
+            .get_or_insert_with(NameMap::new)
+            .append(idx, name);
+    }
+
+    pub fn name_type(&mut self, idx: u32, name: &str) {
+        self.type_names
+            .get_or_insert_with(NameMap::new)
+            .append(idx, name);
+    }
+
+    pub fn name_component(&mut self, idx: u32, name: &str) {
+        self.component_names
+            .get_or_insert_with(NameMap::new)
+            .append(idx, name);
+    }
+
     pub fn instantiate<'a, A>(&mut self, module_index: u32, args: A) -> u32
     where
         A: IntoIterator<Item = (&'a str, ModuleArg)>,
@@ -54,25 +149,49 @@ impl ComponentBuilder {
         inc(&mut self.funcs)
     }
 
-    pub fn lower_func<O>(&mut self, func_index: u32, options: O) -> u32
-    where
-        O: IntoIterator<Item = CanonicalOption>,
-        O::IntoIter: ExactSizeIterator,
-    {
-        self.canonical_functions().lower(func_index, options);
+    pub fn lower_func(&mut self, func_index: u32, options: CanonicalOptions) -> u32 {
+        assert!(
+            options.post_return.is_none(),
+            "`post-return` is only meaningful on `lift`, not `lower`"
+        );
+        self.validate_options(&options);
+        self.canonical_functions().lower(func_index, options.into_vec());
         inc(&mut self.core_funcs)
     }
 
-    pub fn lift_func<O>(&mut self, core_func_index: u32, type_index: u32, options: O) -> u32
-    where
-        O: IntoIterator<Item = CanonicalOption>,
-        O::IntoIter: ExactSizeIterator,
-    {
+    pub fn lift_func(
+        &mut self,
+        core_func_index: u32,
+        type_index: u32,
+        options: CanonicalOptions,
+    ) -> u32 {
+        self.validate_options(&options);
         self.canonical_functions()
-            .lift(core_func_index, type_index, options);
+            .lift(core_func_index, type_index, options.into_vec());
         inc(&mut self.funcs)
     }
 
+    fn validate_options(&self, options: &CanonicalOptions) {
+        if let Some(memory) = options.memory {
+            assert!(
+                memory < self.core_memories,
+                "`memory` option refers to a core memory that hasn't been allocated yet"
+            );
+        }
+        if let Some(realloc) = options.realloc {
+            assert!(
+                realloc < self.core_funcs,
+                "`realloc` option refers to a core func that hasn't been allocated yet"
+            );
+        }
+        if let Some(post_return) = options.post_return {
+            assert!(
+                post_return < self.core_funcs,
+                "`post-return` option refers to a core func that hasn't been allocated yet"
+            );
+        }
+    }
+
     pub fn instantiate_core_exports<'a, E>(&mut self, exports: E) -> u32
     where
         E: IntoIterator<Item = (&'a str, ExportKind, u32)>,
@@ -106,6 +225,41 @@ impl ComponentBuilder {
         inc(&mut self.core_modules)
     }
 
+    pub fn component(&mut self, component: &Component) -> u32 {
+        self.flush();
+        self.component.section(&NestedComponentSection(component));
+        inc(&mut self.components)
+    }
+
+    pub fn component_raw(&mut self, bytes: &[u8]) -> u32 {
+        self.flush();
+        self.component.section(&wasm_encoder::RawSection {
+            id: ComponentSectionId::Component.into(),
+            data: bytes,
+        });
+        inc(&mut self.components)
+    }
+
+    /// Declares the start function of this component, which may be called at
+    /// most once.
+    ///
+    /// Returns the newly-allocated value indices, one per declared result of
+    /// the start function.
+    pub fn start<A>(&mut self, func_index: u32, args: A, results: u32) -> Vec<u32>
+    where
+        A: IntoIterator<Item = u32>,
+    {
+        assert!(!self.has_start, "a component may only have one start function");
+        self.has_start = true;
+        self.flush();
+        self.component.section(&ComponentStartSection {
+            function_index: func_index,
+            args: args.into_iter().collect::<Vec<_>>(),
+            results,
+        });
+        (0..results).map(|_| inc(&mut self.values)).collect()
+    }
+
     pub fn alias_core_item(&mut self, instance: u32, kind: ExportKind, name: &str) -> u32 {
         self.aliases().alias(Alias::CoreInstanceExport {
             instance,
@@ -127,7 +281,8 @@ impl ComponentBuilder {
             ComponentExportKind::Func => inc(&mut self.funcs),
             ComponentExportKind::Module => inc(&mut self.core_modules),
             ComponentExportKind::Instance => inc(&mut self.instances),
-            ComponentExportKind::Component | ComponentExportKind::Value => unimplemented!(),
+            ComponentExportKind::Component => inc(&mut self.components),
+            ComponentExportKind::Value => inc(&mut self.values),
         }
     }
 
@@ -135,6 +290,8 @@ impl ComponentBuilder {
         let ret = match &ty {
             ComponentTypeRef::Instance(_) => inc(&mut self.instances),
             ComponentTypeRef::Func(_) => inc(&mut self.funcs),
+            ComponentTypeRef::Component(_) => inc(&mut self.components),
+            ComponentTypeRef::Value(_) => inc(&mut self.values),
             _ => unimplemented!(),
         };
         self.imports().import(name, url, ty);
@@ -155,6 +312,10 @@ impl ComponentBuilder {
         (inc(&mut self.types), self.types().function())
     }
 
+    pub fn core_type(&mut self) -> (u32, CoreTypeEncoder<'_>) {
+        (inc(&mut self.core_types), self.core_types().core_type())
+    }
+
     pub fn alias_type_export(&mut self, instance: u32, name: &str) -> u32 {
         self.aliases().alias(Alias::InstanceExport {
             instance,
@@ -172,6 +333,24 @@ impl ComponentBuilder {
         });
         inc(&mut self.types)
     }
+
+    pub fn alias_outer_component(&mut self, count: u32, index: u32) -> u32 {
+        self.aliases().alias(Alias::Outer {
+            count,
+            kind: ComponentOuterAliasKind::Component,
+            index,
+        });
+        inc(&mut self.components)
+    }
+
+    pub fn alias_outer_core_type(&mut self, count: u32, index: u32) -> u32 {
+        self.aliases().alias(Alias::Outer {
+            count,
+            kind: ComponentOuterAliasKind::CoreType,
+            index,
+        });
+        inc(&mut self.core_types)
+    }
 }
 
 // Helper macro to generate methods on `ComponentBuilder` to get specific
@@ -234,6 +413,7 @@ section_accessors! {
     exports => ComponentExportSection
     imports => ComponentImportSection
     types => ComponentTypeSection
+    core_types => CoreTypeSection
 }
 
 fn inc(idx: &mut u32) -> u32 {
@@ -241,3 +421,71 @@ fn inc(idx: &mut u32) -> u32 {
     *idx += 1;
     ret
 }
+
+/// Builder for the options accepted by canonical `lower`/`lift` functions,
+/// ensuring by construction that the resulting list of `CanonicalOption`s is
+/// well-formed (e.g. at most one string encoding is specified).
+#[derive(Default)]
+pub struct CanonicalOptions {
+    string_encoding: Option<CanonicalOption>,
+    memory: Option<u32>,
+    realloc: Option<u32>,
+    post_return: Option<u32>,
+}
+
+impl CanonicalOptions {
+    fn set_string_encoding(&mut self, encoding: CanonicalOption) {
+        assert!(
+            self.string_encoding.is_none(),
+            "at most one string encoding option may be specified"
+        );
+        self.string_encoding = Some(encoding);
+    }
+
+    pub fn string_utf8(mut self) -> Self {
+        self.set_string_encoding(CanonicalOption::UTF8);
+        self
+    }
+
+    pub fn string_utf16(mut self) -> Self {
+        self.set_string_encoding(CanonicalOption::UTF16);
+        self
+    }
+
+    pub fn string_latin1_utf16(mut self) -> Self {
+        self.set_string_encoding(CanonicalOption::CompactUTF16);
+        self
+    }
+
+    pub fn memory(mut self, memory_index: u32) -> Self {
+        self.memory = Some(memory_index);
+        self
+    }
+
+    pub fn realloc(mut self, core_func_index: u32) -> Self {
+        self.realloc = Some(core_func_index);
+        self
+    }
+
+    pub fn post_return(mut self, core_func_index: u32) -> Self {
+        self.post_return = Some(core_func_index);
+        self
+    }
+
+    fn into_vec(self) -> Vec<CanonicalOption> {
+        let mut options = Vec::new();
+        if let Some(encoding) = self.string_encoding {
+            options.push(encoding);
+        }
+        if let Some(memory) = self.memory {
+            options.push(CanonicalOption::Memory(memory));
+        }
+        if let Some(realloc) = self.realloc {
+            options.push(CanonicalOption::Realloc(realloc));
+        }
+        if let Some(post_return) = self.post_return {
+            options.push(CanonicalOption::PostReturn(post_return));
+        }
+        options
+    }
+}